@@ -7,14 +7,16 @@ extern crate cairo_sys;
 extern crate css_color_parser;
 extern crate font_loader;
 extern crate itertools;
+extern crate pango;
+extern crate pangocairo;
 extern crate pretty_env_logger;
 extern crate x11;
 extern crate xcb;
 extern crate xcb_util;
 extern crate xkbcommon;
 
-use cairo::enums::{FontSlant, FontWeight};
 use cairo::prelude::SurfaceExt;
+use pango::LayoutExt;
 use std::ffi::CStr;
 use xkbcommon::xkb;
 
@@ -32,6 +34,15 @@ mod wm_i3;
 #[cfg(feature = "i3")]
 use wm_i3 as wm;
 
+// Works under any `_NET`-compliant window manager, not just i3 -- enable with `--features ewmh`
+// instead when not running i3. Don't enable both features at once; both would import a `wm`
+// into this module, which fails to compile.
+#[cfg(feature = "ewmh")]
+mod wm_ewmh;
+
+#[cfg(feature = "ewmh")]
+use wm_ewmh as wm;
+
 #[derive(Debug)]
 pub struct DesktopWindow {
     id: i64,
@@ -44,6 +55,12 @@ pub struct RenderWindow<'a> {
     desktop_window: &'a DesktopWindow,
     cairo_context: cairo::Context,
     draw_pos: (f64, f64),
+    // The generated hint label for this window. Used to match keystrokes in `Hints` mode;
+    // ignored in `Search` mode, where `desktop_window.title` is matched and drawn instead.
+    hint: String,
+    // The id of the xcb window we drew this hint into, so BUTTON_PRESS/ENTER_NOTIFY/LEAVE_NOTIFY
+    // events (which carry only a window id) can be mapped back to the RenderWindow they hit.
+    xcb_window_id: xcb::Window,
 }
 
 #[derive(Debug)]
@@ -54,14 +71,18 @@ pub struct AppConfig {
     pub margin: f32,
     pub text_color: (f64, f64, f64, f64),
     pub bg_color: (f64, f64, f64, f64),
+    pub highlight_color: (f64, f64, f64, f64),
     pub fill: bool,
     pub horizontal_align: utils::HorizontalAlign,
     pub vertical_align: utils::VerticalAlign,
+    pub exit_on_mismatch: bool,
+    pub mode: utils::SelectionMode,
+    pub transparency: bool,
 }
 
 static HINT_CHARS: &'static str = "sadfjklewcmpgh";
 
-#[cfg(any(feature = "i3", feature = "add_some_other_wm_here"))]
+#[cfg(any(feature = "i3", feature = "ewmh"))]
 fn main() {
     let app_config = utils::parse_args();
 
@@ -72,56 +93,97 @@ fn main() {
     let setup = conn.get_setup();
     let screen = setup.roots().nth(screen_num as usize).unwrap();
 
-    let values = [
-        (
-            xcb::CW_EVENT_MASK,
-            xcb::EVENT_MASK_EXPOSURE
-                | xcb::EVENT_MASK_KEY_PRESS
-                | xcb::EVENT_MASK_BUTTON_PRESS
-                | xcb::EVENT_MASK_BUTTON_RELEASE,
+    let event_mask = xcb::EVENT_MASK_EXPOSURE
+        | xcb::EVENT_MASK_KEY_PRESS
+        | xcb::EVENT_MASK_BUTTON_PRESS
+        | xcb::EVENT_MASK_BUTTON_RELEASE
+        | xcb::EVENT_MASK_ENTER_WINDOW
+        | xcb::EVENT_MASK_LEAVE_WINDOW;
+
+    // Look for a 32-bit ARGB visual so hint windows can be genuinely translucent under a
+    // compositor; against the root visual, `bg_color`'s alpha channel is simply ignored by the
+    // server. A dedicated colormap is mandatory for the server to accept a non-root visual.
+    let transparent_visual = if app_config.transparency {
+        utils::find_alpha_visual(&screen).map(|visual| {
+            let colormap = conn.generate_id();
+            xcb::create_colormap(
+                &conn,
+                xcb::COLORMAP_ALLOC_NONE as u8,
+                colormap,
+                screen.root(),
+                visual.visual_id(),
+            );
+            (visual, colormap)
+        })
+    } else {
+        None
+    };
+
+    let (depth, window_visual, values) = match &transparent_visual {
+        Some((visual, colormap)) => (
+            32u8,
+            visual.visual_id(),
+            vec![
+                (xcb::CW_BACK_PIXEL, 0),
+                (xcb::CW_BORDER_PIXEL, 0),
+                (xcb::CW_EVENT_MASK, event_mask),
+                (xcb::CW_OVERRIDE_REDIRECT, 1),
+                (xcb::CW_COLORMAP, *colormap),
+            ],
         ),
-        (xcb::CW_OVERRIDE_REDIRECT, 1),
-    ];
+        None => (
+            xcb::COPY_FROM_PARENT as u8,
+            screen.root_visual(),
+            vec![(xcb::CW_EVENT_MASK, event_mask), (xcb::CW_OVERRIDE_REDIRECT, 1)],
+        ),
+    };
+
+    // Generate all hint labels up front: a prefix-free set sized to the number of windows, so
+    // that incremental matching below never has to guess whether a typed prefix is "done".
+    let hints = utils::get_hints(desktop_windows.len(), HINT_CHARS);
+
+    let mut render_windows = Vec::with_capacity(desktop_windows.len());
+    for (desktop_window, hint) in desktop_windows.iter().zip(hints.into_iter()) {
+        // What's drawn (and initially sized for) depends on the selection mode: a short
+        // generated hint, or the window's own title for fuzzy search.
+        let label = match app_config.mode {
+            utils::SelectionMode::Hints => hint.clone(),
+            utils::SelectionMode::Search => desktop_window.title.clone(),
+        };
 
-    let mut render_windows = HashMap::new();
-    for desktop_window in &desktop_windows {
         // We need to estimate the font size before rendering because we want the window to only be
         // the size of the font.
-        let hint = utils::get_next_hint(
-            render_windows.keys().collect(),
-            HINT_CHARS,
-            desktop_windows.len(),
-        );
 
         // Figure out how large the window actually needs to be.
-        let text_extents =
-            utils::extents_for_text(&hint, &app_config.font_family, app_config.font_size);
+        let (text_width, text_height) =
+            utils::text_pixel_size(&label, &app_config.font_family, app_config.font_size);
+        let (text_width, text_height) = (f64::from(text_width), f64::from(text_height));
+        // An empty label (an unnamed window in Search mode) measures zero pixels wide; clamp
+        // both dimensions to at least the font size so `create_window` never sees a zero
+        // dimension, which is a protocol error.
+        let min_size = app_config.font_size.max(1.0);
+        let text_width = text_width.max(min_size);
+        let text_height = text_height.max(min_size);
         let (width, height, margin_width, margin_height) = if app_config.fill {
             (
                 desktop_window.size.0 as u16,
                 desktop_window.size.1 as u16,
-                (f64::from(desktop_window.size.0) - text_extents.width) / 2.0,
-                (f64::from(desktop_window.size.1) - text_extents.height) / 2.0,
+                (f64::from(desktop_window.size.0) - text_width) / 2.0,
+                (f64::from(desktop_window.size.1) - text_height) / 2.0,
             )
         } else {
             let margin_factor = 1.0 + 0.2;
             (
-                (text_extents.width * margin_factor).round() as u16,
-                (text_extents.height * margin_factor).round() as u16,
-                ((text_extents.width * margin_factor) - text_extents.width) / 2.0,
-                ((text_extents.height * margin_factor) - text_extents.height) / 2.0,
+                (text_width * margin_factor).round() as u16,
+                (text_height * margin_factor).round() as u16,
+                ((text_width * margin_factor) - text_width) / 2.0,
+                ((text_height * margin_factor) - text_height) / 2.0,
             )
         };
 
-        // Due to the way cairo lays out text, we'll have to calculate the actual coordinates to
-        // put the cursor. See:
-        // https://www.cairographics.org/samples/text_align_center/
-        // https://www.cairographics.org/samples/text_extents/
-        // https://www.cairographics.org/tutorial/#L1understandingtext
-        let draw_pos = (
-            margin_width - text_extents.x_bearing,
-            text_extents.height + margin_height - (text_extents.height + text_extents.y_bearing),
-        );
+        // Pango lays out text from the top-left corner it's drawn at, so unlike cairo's toy font
+        // API there's no bearing correction needed here: the margin itself is the draw position.
+        let draw_pos = (margin_width, margin_height);
 
         debug!(
             "Spawning RenderWindow for this DesktopWindow: {:?}",
@@ -153,7 +215,7 @@ fn main() {
         // Create the actual window.
         xcb::create_window(
             &conn,
-            xcb::COPY_FROM_PARENT as u8,
+            depth,
             xcb_window_id,
             screen.root(),
             x,
@@ -162,7 +224,7 @@ fn main() {
             height,
             0,
             xcb::WINDOW_CLASS_INPUT_OUTPUT as u16,
-            screen.root_visual(),
+            window_visual,
             &values,
         );
 
@@ -182,7 +244,7 @@ fn main() {
 
         conn.flush();
 
-        let mut visual = utils::find_visual(&conn, screen.root_visual()).unwrap();
+        let mut visual = utils::find_visual(&conn, window_visual).unwrap();
         let cairo_xcb_conn = unsafe {
             cairo::XCBConnection::from_raw_none(
                 conn.get_raw_conn() as *mut cairo_sys::xcb_connection_t
@@ -206,11 +268,21 @@ fn main() {
             desktop_window,
             cairo_context,
             draw_pos,
+            hint,
+            xcb_window_id,
         };
 
-        render_windows.insert(hint, render_window);
+        render_windows.push(render_window);
     }
 
+    // Map each created window's xcb id back to its RenderWindow, so hotspot events (which only
+    // carry a window id) can be dispatched to the window they actually hit.
+    let window_lookup: HashMap<xcb::Window, usize> = render_windows
+        .iter()
+        .enumerate()
+        .map(|(i, rw)| (rw.xcb_window_id, i))
+        .collect();
+
     // Receive keyboard events.
     let grab_keyboard_cookie = xcb::xproto::grab_keyboard(
         &conn,
@@ -229,7 +301,8 @@ fn main() {
         &conn,
         true,
         screen.root(),
-        xcb::EVENT_MASK_BUTTON_PRESS as u16,
+        (xcb::EVENT_MASK_BUTTON_PRESS | xcb::EVENT_MASK_ENTER_WINDOW | xcb::EVENT_MASK_LEAVE_WINDOW)
+            as u16,
         xcb::GRAB_MODE_ASYNC as u8,
         xcb::GRAB_MODE_ASYNC as u8,
         xcb::NONE,
@@ -240,6 +313,14 @@ fn main() {
         .get_reply()
         .expect("Couldn't grab mouse");
 
+    // Characters typed so far. In `Hints` mode this narrows down hint labels by prefix; in
+    // `Search` mode it's a fuzzy query matched against window titles.
+    let mut typed = String::new();
+
+    // The hint window the mouse is currently over, if any; drawn with `highlight_color` so a
+    // hotspot's click target is obvious before you click it.
+    let mut hovered: Option<xcb::Window> = None;
+
     let mut closed = false;
     while !closed {
         let event = conn.wait_for_event();
@@ -250,34 +331,53 @@ fn main() {
             Some(event) => {
                 let r = event.response_type();
                 match r {
-                    xcb::EXPOSE => {
-                        for (hint, rw) in &render_windows {
-                            rw.cairo_context.set_source_rgba(
-                                app_config.bg_color.0,
-                                app_config.bg_color.1,
-                                app_config.bg_color.2,
-                                app_config.bg_color.3,
-                            );
-                            rw.cairo_context.paint();
-                            rw.cairo_context.select_font_face(
-                                &app_config.font_family,
-                                FontSlant::Normal,
-                                FontWeight::Normal,
-                            );
-                            rw.cairo_context.set_font_size(app_config.font_size);
-                            rw.cairo_context.move_to(rw.draw_pos.0, rw.draw_pos.1);
-                            rw.cairo_context.set_source_rgba(
-                                app_config.text_color.0,
-                                app_config.text_color.1,
-                                app_config.text_color.2,
-                                app_config.text_color.3,
-                            );
-                            rw.cairo_context.show_text(&hint);
-                            rw.cairo_context.get_target().flush();
-                            conn.flush();
+                    xcb::EXPOSE => match app_config.mode {
+                        utils::SelectionMode::Hints => {
+                            redraw_hints(&conn, &render_windows, &typed, hovered, &app_config)
+                        }
+                        utils::SelectionMode::Search => {
+                            redraw_search(&conn, &render_windows, &typed, hovered, &app_config)
+                        }
+                    },
+                    xcb::ENTER_NOTIFY => {
+                        let enter: &xcb::EnterNotifyEvent = unsafe { xcb::cast_event(&event) };
+                        // `grab_pointer` synthesizes NotifyGrab/NotifyUngrab crossing events for
+                        // whatever window the pointer already sits over; only NotifyNormal means
+                        // the pointer actually moved into this window.
+                        if enter.mode() == xcb::NOTIFY_MODE_NORMAL as u8 {
+                            hovered = Some(enter.event());
+                            match app_config.mode {
+                                utils::SelectionMode::Hints => {
+                                    redraw_hints(&conn, &render_windows, &typed, hovered, &app_config)
+                                }
+                                utils::SelectionMode::Search => {
+                                    redraw_search(&conn, &render_windows, &typed, hovered, &app_config)
+                                }
+                            }
+                        }
+                    }
+                    xcb::LEAVE_NOTIFY => {
+                        let leave: &xcb::LeaveNotifyEvent = unsafe { xcb::cast_event(&event) };
+                        if leave.mode() == xcb::NOTIFY_MODE_NORMAL as u8 && hovered == Some(leave.event()) {
+                            hovered = None;
+                            match app_config.mode {
+                                utils::SelectionMode::Hints => {
+                                    redraw_hints(&conn, &render_windows, &typed, hovered, &app_config)
+                                }
+                                utils::SelectionMode::Search => {
+                                    redraw_search(&conn, &render_windows, &typed, hovered, &app_config)
+                                }
+                            }
                         }
                     }
                     xcb::BUTTON_PRESS => {
+                        let button_press: &xcb::ButtonPressEvent =
+                            unsafe { xcb::cast_event(&event) };
+                        // A click inside a hint window's hotspot focuses that specific window;
+                        // a click anywhere else (e.g. the root window) just closes wmfocus.
+                        if let Some(&i) = window_lookup.get(&button_press.event()) {
+                            wm::focus_window(&render_windows[i].desktop_window);
+                        }
                         closed = true;
                     }
                     xcb::KEY_PRESS => {
@@ -285,17 +385,72 @@ fn main() {
 
                         let syms = xcb_util::keysyms::KeySymbols::new(&conn);
                         let ksym = syms.press_lookup_keysym(key_press, 0);
-                        let kstr = unsafe {
-                            CStr::from_ptr(x11::xlib::XKeysymToString(ksym.into()))
-                                .to_str()
-                                .expect("Couldn't create Rust string from C string")
-                        };
+
                         if ksym == xkb::KEY_Escape {
                             closed = true;
+                        } else if ksym == xkb::KEY_BackSpace {
+                            typed.pop();
+                        } else if app_config.mode == utils::SelectionMode::Search
+                            && (ksym == xkb::KEY_Return || ksym == xkb::KEY_KP_Enter)
+                        {
+                            if let Some((rw, _, _)) = best_search_match(&render_windows, &typed) {
+                                wm::focus_window(&rw.desktop_window);
+                                closed = true;
+                            }
+                        } else {
+                            let kstr = unsafe {
+                                CStr::from_ptr(x11::xlib::XKeysymToString(ksym.into()))
+                                    .to_str()
+                                    .expect("Couldn't create Rust string from C string")
+                            };
+
+                            if kstr.chars().count() == 1 {
+                                match app_config.mode {
+                                    utils::SelectionMode::Hints => {
+                                        let mut candidate = typed.clone();
+                                        candidate.push_str(kstr);
+
+                                        let surviving = render_windows
+                                            .iter()
+                                            .filter(|rw| rw.hint.starts_with(&candidate))
+                                            .count();
+
+                                        if surviving == 0 {
+                                            // No hint matches this keystroke; either bail out or
+                                            // just ignore it and keep waiting for a valid one.
+                                            if app_config.exit_on_mismatch {
+                                                closed = true;
+                                            }
+                                        } else {
+                                            typed = candidate;
+                                            // `surviving == 1` only means one hint *starts with*
+                                            // `typed`; since hints can be longer than their
+                                            // shortest distinguishing prefix, only auto-focus once
+                                            // `typed` is itself a complete hint.
+                                            if let Some(rw) =
+                                                render_windows.iter().find(|rw| rw.hint == typed)
+                                            {
+                                                wm::focus_window(&rw.desktop_window);
+                                                closed = true;
+                                            }
+                                        }
+                                    }
+                                    utils::SelectionMode::Search => {
+                                        typed.push_str(kstr);
+                                    }
+                                }
+                            }
                         }
-                        if let Some(rw) = &render_windows.get(kstr) {
-                            wm::focus_window(&rw.desktop_window);
-                            closed = true;
+
+                        if !closed {
+                            match app_config.mode {
+                                utils::SelectionMode::Hints => {
+                                    redraw_hints(&conn, &render_windows, &typed, hovered, &app_config)
+                                }
+                                utils::SelectionMode::Search => {
+                                    redraw_search(&conn, &render_windows, &typed, hovered, &app_config)
+                                }
+                            }
                         }
                     }
                     _ => {}
@@ -305,11 +460,145 @@ fn main() {
     }
 }
 
-#[cfg(not(any(feature = "i3", feature = "add_some_other_wm_here")))]
+/// Paint every hint window whose label still starts with `typed`, dimming the already-typed
+/// prefix so the remaining keystrokes stand out. Windows that have been filtered out by `typed`
+/// are skipped: they stay mapped and keep showing whatever was last drawn into them.
+fn redraw_hints(
+    conn: &xcb::Connection,
+    render_windows: &[RenderWindow],
+    typed: &str,
+    hovered: Option<xcb::Window>,
+    app_config: &AppConfig,
+) {
+    let full_alpha = (app_config.text_color.3 * 100.0).round() as u8;
+    let dim_alpha = (app_config.text_color.3 * 0.4 * 100.0).round() as u8;
+
+    for rw in render_windows {
+        if !rw.hint.starts_with(typed) {
+            continue;
+        }
+
+        let color_hex = if hovered == Some(rw.xcb_window_id) {
+            utils::to_hex_color(app_config.highlight_color)
+        } else {
+            utils::to_hex_color(app_config.text_color)
+        };
+
+        rw.cairo_context.set_source_rgba(
+            app_config.bg_color.0,
+            app_config.bg_color.1,
+            app_config.bg_color.2,
+            app_config.bg_color.3,
+        );
+        rw.cairo_context.paint();
+
+        let (typed_part, rest_part) = rw.hint.split_at(typed.len());
+        let markup = format!(
+            "<span foreground=\"{0}\" alpha=\"{1}%\">{2}</span><span foreground=\"{0}\" alpha=\"{3}%\">{4}</span>",
+            color_hex,
+            dim_alpha,
+            utils::escape_markup(typed_part),
+            full_alpha,
+            utils::escape_markup(rest_part),
+        );
+
+        let layout = utils::make_layout(&rw.cairo_context, &app_config.font_family, app_config.font_size);
+        layout.set_markup(&markup);
+        rw.cairo_context.move_to(rw.draw_pos.0, rw.draw_pos.1);
+        pangocairo::functions::show_layout(&rw.cairo_context, &layout);
+
+        rw.cairo_context.get_target().flush();
+    }
+    conn.flush();
+}
+
+/// The currently best-scoring fuzzy match for `typed`, if any window's title matches at all.
+fn best_search_match<'r, 'a>(
+    render_windows: &'r [RenderWindow<'a>],
+    typed: &str,
+) -> Option<(&'r RenderWindow<'a>, i64, Vec<usize>)> {
+    render_windows
+        .iter()
+        .filter_map(|rw| {
+            utils::fuzzy_match(typed, &rw.desktop_window.title)
+                .map(|(score, indices)| (rw, score, indices))
+        })
+        .max_by_key(|(_, score, _)| *score)
+}
+
+/// Paint every hint window whose title still fuzzy-matches `typed`, dimming the characters that
+/// didn't match within the title (changing weight instead of alpha would change glyph widths and
+/// clip a title against the window sized for its plain extents). The best-scoring match (the one
+/// `Enter` would focus) is drawn with the text color at full strength; the rest are dimmed
+/// slightly.
+fn redraw_search(
+    conn: &xcb::Connection,
+    render_windows: &[RenderWindow],
+    typed: &str,
+    hovered: Option<xcb::Window>,
+    app_config: &AppConfig,
+) {
+    let best = best_search_match(render_windows, typed).map(|(rw, _, _)| rw.desktop_window.id);
+
+    for rw in render_windows {
+        let matched_indices = match utils::fuzzy_match(typed, &rw.desktop_window.title) {
+            Some((_, indices)) => indices,
+            None => continue,
+        };
+
+        let color_hex = if hovered == Some(rw.xcb_window_id) {
+            utils::to_hex_color(app_config.highlight_color)
+        } else {
+            utils::to_hex_color(app_config.text_color)
+        };
+
+        rw.cairo_context.set_source_rgba(
+            app_config.bg_color.0,
+            app_config.bg_color.1,
+            app_config.bg_color.2,
+            app_config.bg_color.3,
+        );
+        rw.cairo_context.paint();
+
+        let is_best = best == Some(rw.desktop_window.id);
+        let window_alpha = if is_best {
+            app_config.text_color.3
+        } else {
+            app_config.text_color.3 * 0.6
+        };
+
+        let mut markup = String::new();
+        for (i, c) in rw.desktop_window.title.chars().enumerate() {
+            let escaped = utils::escape_markup(&c.to_string());
+            let char_alpha = if matched_indices.contains(&i) {
+                window_alpha
+            } else {
+                window_alpha * 0.6
+            };
+            markup.push_str(&format!(
+                "<span foreground=\"{}\" alpha=\"{}%\">{}</span>",
+                color_hex,
+                (char_alpha * 100.0).round() as u8,
+                escaped
+            ));
+        }
+
+        let layout = utils::make_layout(&rw.cairo_context, &app_config.font_family, app_config.font_size);
+        layout.set_markup(&markup);
+        rw.cairo_context.move_to(rw.draw_pos.0, rw.draw_pos.1);
+        pangocairo::functions::show_layout(&rw.cairo_context, &layout);
+
+        rw.cairo_context.get_target().flush();
+    }
+    conn.flush();
+}
+
+#[cfg(not(any(feature = "i3", feature = "ewmh")))]
 fn main() {
     eprintln!(
         "You need to enable to enabe support for at least one window manager.\n
 Currently supported:
-    --features i3"
+    --features i3
+    --features ewmh"
     );
 }
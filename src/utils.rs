@@ -0,0 +1,326 @@
+use cairo;
+use clap::{App, Arg};
+use css_color_parser::Color as CssColor;
+use font_loader::system_fonts;
+use pango;
+use pango::LayoutExt;
+use pangocairo;
+
+use crate::AppConfig;
+
+arg_enum! {
+    #[derive(Debug)]
+    pub enum HorizontalAlign {
+        Left,
+        Center,
+        Right,
+    }
+}
+
+arg_enum! {
+    #[derive(Debug)]
+    pub enum VerticalAlign {
+        Top,
+        Center,
+        Bottom,
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, PartialEq)]
+    pub enum SelectionMode {
+        Hints,
+        Search,
+    }
+}
+
+fn parse_color(src: &str) -> Result<(f64, f64, f64, f64), String> {
+    let color = src
+        .parse::<CssColor>()
+        .map_err(|_| format!("Couldn't parse color '{}'", src))?;
+    Ok((
+        f64::from(color.r) / 255.0,
+        f64::from(color.g) / 255.0,
+        f64::from(color.b) / 255.0,
+        f64::from(color.a),
+    ))
+}
+
+pub fn parse_args() -> AppConfig {
+    let matches = App::new(crate_name!())
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about(crate_description!())
+        .arg(
+            Arg::with_name("font")
+                .short("f")
+                .long("font")
+                .help("Which font to use")
+                .default_value("Mono:size=12"),
+        )
+        .arg(
+            Arg::with_name("margin")
+                .long("margin")
+                .help("Parsed but not applied: hint boxes are padded by 10% of the text size on each side")
+                .default_value("10"),
+        )
+        .arg(
+            Arg::with_name("text_color")
+                .long("textcolor")
+                .help("Text color, HTML notation")
+                .default_value("#dddddd"),
+        )
+        .arg(
+            Arg::with_name("bg_color")
+                .long("bgcolor")
+                .help("Background color, HTML notation")
+                .default_value("rgba(30, 30, 30, 0.9)"),
+        )
+        .arg(
+            Arg::with_name("highlight_color")
+                .long("highlightcolor")
+                .help("Text color for the hint currently hovered by the mouse, HTML notation")
+                .default_value("#ff0000"),
+        )
+        .arg(
+            Arg::with_name("fill")
+                .long("fill")
+                .help("Fill the whole window instead of sizing to the hint text"),
+        )
+        .arg(
+            Arg::with_name("exit_on_mismatch")
+                .long("exitonmismatch")
+                .help("Exit instead of staying open when a keystroke doesn't match any hint"),
+        )
+        .arg(
+            Arg::with_name("transparency")
+                .long("transparency")
+                .help("Use a 32-bit ARGB visual so bg_color's alpha channel is respected (requires a compositor)"),
+        )
+        .arg(
+            Arg::with_name("mode")
+                .long("mode")
+                .help("How to pick a window: by typing a generated hint, or by typing part of its title")
+                .possible_values(&SelectionMode::variants())
+                .case_insensitive(true)
+                .default_value("Hints"),
+        )
+        .arg(
+            Arg::with_name("horizontal_align")
+                .long("horizontalalign")
+                .help("Horizontal alignment of the hint box within the window")
+                .possible_values(&HorizontalAlign::variants())
+                .case_insensitive(true)
+                .default_value("Left"),
+        )
+        .arg(
+            Arg::with_name("vertical_align")
+                .long("verticalalign")
+                .help("Vertical alignment of the hint box within the window")
+                .possible_values(&VerticalAlign::variants())
+                .case_insensitive(true)
+                .default_value("Top"),
+        )
+        .get_matches();
+
+    let mut font = matches.value_of("font").unwrap().splitn(2, ":size=");
+    let font_family = font.next().unwrap_or("Mono").to_string();
+    let font_size: f64 = font.next().unwrap_or("12").parse().unwrap_or(12.0);
+
+    let font_property = system_fonts::FontPropertyBuilder::new()
+        .family(&font_family)
+        .build();
+    let (loaded_font, _) = system_fonts::get(&font_property)
+        .unwrap_or_else(|| panic!("Couldn't load font {}", font_family));
+
+    AppConfig {
+        font_family,
+        font_size,
+        loaded_font,
+        margin: matches
+            .value_of("margin")
+            .unwrap()
+            .parse()
+            .expect("Margin needs to be a number"),
+        text_color: parse_color(matches.value_of("text_color").unwrap()).unwrap(),
+        bg_color: parse_color(matches.value_of("bg_color").unwrap()).unwrap(),
+        highlight_color: parse_color(matches.value_of("highlight_color").unwrap()).unwrap(),
+        fill: matches.is_present("fill"),
+        exit_on_mismatch: matches.is_present("exit_on_mismatch"),
+        transparency: matches.is_present("transparency"),
+        horizontal_align: value_t!(matches, "horizontal_align", HorizontalAlign).unwrap(),
+        vertical_align: value_t!(matches, "vertical_align", VerticalAlign).unwrap(),
+        mode: value_t!(matches, "mode", SelectionMode).unwrap(),
+    }
+}
+
+/// Generate `n` distinct, prefix-free hint labels out of `hint_chars`, Vimium-style: the
+/// minimum label length `L` is `ceil(log_k(n))` for a `k`-char alphabet. Every label is either a
+/// single character or exactly `L` characters long, and as many labels as possible are kept at a
+/// single character so that typing a hint usually takes fewer keystrokes. Because the set is prefix-free, no finished label is ever also the prefix of
+/// another one, so incremental matching in the event loop can't get confused about when a label
+/// is "done".
+pub fn get_hints(n: usize, hint_chars: &str) -> Vec<String> {
+    let chars: Vec<char> = hint_chars.chars().collect();
+    let k = chars.len();
+    assert!(k > 1, "need at least two distinct hint characters");
+    if n == 0 {
+        return Vec::new();
+    }
+
+    // Smallest label length that can address all `n` windows.
+    let mut length = 1;
+    while k.pow(length as u32) < n {
+        length += 1;
+    }
+
+    if length == 1 {
+        return chars.into_iter().take(n).map(|c| c.to_string()).collect();
+    }
+
+    // Each first-level character we *expand* buys us `k^(length - 1)` long labels instead of the
+    // one short label it would otherwise be, so total capacity is `(k - e) + e * k^(length - 1)`
+    // for `e` expanded characters. Find the smallest `e` that covers `n` -- capacity is
+    // monotonically increasing in `e`, so the first one that fits is the minimum.
+    let capacity_per_expanded = k.pow((length - 1) as u32);
+    let expand_count = (0..=k)
+        .find(|&e| (k - e) + e * capacity_per_expanded >= n)
+        .unwrap_or(k);
+    let short_count = k - expand_count;
+
+    let mut hints = Vec::with_capacity(n);
+    hints.extend(chars.iter().take(short_count).map(|c| c.to_string()));
+
+    'outer: for &c in &chars[short_count..] {
+        for suffix in odometer(&chars, length - 1) {
+            if hints.len() >= n {
+                break 'outer;
+            }
+            hints.push(format!("{}{}", c, suffix));
+        }
+    }
+
+    hints.truncate(n);
+    hints
+}
+
+/// Every string of exactly `length` characters drawn from `chars`, in the same order as counting
+/// up through the alphabet (the last character varies fastest).
+fn odometer(chars: &[char], length: usize) -> Vec<String> {
+    let mut results = vec![String::new()];
+    for _ in 0..length {
+        let mut next = Vec::with_capacity(results.len() * chars.len());
+        for prefix in &results {
+            for &c in chars {
+                next.push(format!("{}{}", prefix, c));
+            }
+        }
+        results = next;
+    }
+    results
+}
+
+/// Build a `pango::Layout` against `cairo_context` using `font_family`/`font_size`. Shared by
+/// text measurement and the actual drawing, so both always agree on metrics.
+pub fn make_layout(cairo_context: &cairo::Context, font_family: &str, font_size: f64) -> pango::Layout {
+    let layout =
+        pangocairo::functions::create_layout(cairo_context).expect("Couldn't create pango layout");
+    let mut font_desc = pango::FontDescription::new();
+    font_desc.set_family(font_family);
+    font_desc.set_size(font_size as i32 * pango::SCALE);
+    layout.set_font_description(Some(&font_desc));
+    layout
+}
+
+/// Measure the pixel size `text` would take up when laid out with Pango, using `font_family` and
+/// `font_size`. Used to size each hint window before it's created, replacing the cairo "toy" font
+/// API's fragile, hand-rolled extents.
+pub fn text_pixel_size(text: &str, font_family: &str, font_size: f64) -> (i32, i32) {
+    let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 0, 0)
+        .expect("Couldn't create temporary surface for text measurement");
+    let cairo_context = cairo::Context::new(&surface);
+    let layout = make_layout(&cairo_context, font_family, font_size);
+    layout.set_text(text);
+    layout.get_pixel_size()
+}
+
+/// Escape the characters Pango markup treats specially.
+pub fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Render an RGB color (ignoring alpha, which Pango markup expresses separately via the `alpha`
+/// attribute) as the `#rrggbb` string Pango markup expects.
+pub fn to_hex_color(color: (f64, f64, f64, f64)) -> String {
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        (color.0 * 255.0).round() as u8,
+        (color.1 * 255.0).round() as u8,
+        (color.2 * 255.0).round() as u8,
+    )
+}
+
+/// Walk every screen's allowed depths looking for a visual matching `visual_id`.
+pub fn find_visual(conn: &xcb::Connection, visual_id: xcb::Visualid) -> Option<xcb::Visualtype> {
+    for screen in conn.get_setup().roots() {
+        for depth in screen.allowed_depths() {
+            for visual in depth.visuals() {
+                if visual.visual_id() == visual_id {
+                    return Some(visual);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Find a 32-bit (i.e. with an alpha channel) visual on `screen`, if the X server advertises one
+/// — typically only true when a compositor is running. Used to make hint windows genuinely
+/// translucent instead of silently ignoring `bg_color`'s alpha channel.
+pub fn find_alpha_visual(screen: &xcb::Screen<'_>) -> Option<xcb::Visualtype> {
+    screen
+        .allowed_depths()
+        .filter(|depth| depth.depth() == 32)
+        .find_map(|depth| depth.visuals().next())
+}
+
+/// Fuzzy-match `pattern` against `text` as a subsequence, case-insensitively. Returns `None` if
+/// `pattern` isn't a subsequence of `text`, otherwise a score (higher is better) together with
+/// the indices in `text` that were matched, so callers can e.g. bold them. Scoring rewards
+/// contiguous runs, matches that start a word, an overall earlier first match, and shorter
+/// titles, mirroring how a fuzzy launcher ranks its candidates.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    if pattern.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    let text_chars: Vec<char> = text.chars().collect();
+    let mut matched_indices = Vec::with_capacity(pattern.len());
+    let mut text_idx = 0;
+    let mut score: i64 = 0;
+
+    for pc in pattern.chars() {
+        let pc_lower = pc.to_ascii_lowercase();
+        let idx = (text_idx..text_chars.len())
+            .find(|&i| text_chars[i].to_ascii_lowercase() == pc_lower)?;
+
+        if let Some(&prev) = matched_indices.last() {
+            if idx == prev + 1 {
+                score += 10; // contiguous with the previous match
+            }
+        }
+        if idx == 0 || !text_chars[idx - 1].is_alphanumeric() {
+            score += 8; // starts a word
+        }
+
+        matched_indices.push(idx);
+        text_idx = idx + 1;
+    }
+
+    score -= matched_indices[0] as i64; // earlier matches score higher
+    score -= (text_chars.len() as i64) / 4; // shorter titles score higher
+
+    Some((score, matched_indices))
+}
@@ -0,0 +1,151 @@
+use xcb;
+
+use crate::DesktopWindow;
+
+/// Intern an atom by name, panicking if the server can't resolve it (it always can for
+/// well-known EWMH atoms, compliant WM or not).
+fn atom(conn: &xcb::Connection, name: &str) -> xcb::Atom {
+    xcb::intern_atom(conn, false, name)
+        .get_reply()
+        .unwrap_or_else(|_| panic!("Couldn't intern atom {}", name))
+        .atom()
+}
+
+/// Prefer `_NET_WM_NAME` (UTF-8), falling back to the legacy `WM_NAME` for clients that don't set
+/// it.
+fn window_title(
+    conn: &xcb::Connection,
+    window: xcb::Window,
+    net_wm_name: xcb::Atom,
+    utf8_string: xcb::Atom,
+) -> String {
+    let net_wm_name_reply =
+        xcb::get_property(conn, false, window, net_wm_name, utf8_string, 0, 1024).get_reply();
+    if let Ok(reply) = net_wm_name_reply {
+        if let Ok(title) = String::from_utf8(reply.value().to_vec()) {
+            if !title.is_empty() {
+                return title;
+            }
+        }
+    }
+
+    let wm_name_reply = xcb::get_property(
+        conn,
+        false,
+        window,
+        xcb::ATOM_WM_NAME,
+        xcb::ATOM_STRING,
+        0,
+        1024,
+    )
+    .get_reply();
+    wm_name_reply
+        .ok()
+        .and_then(|reply| String::from_utf8(reply.value().to_vec()).ok())
+        .unwrap_or_default()
+}
+
+/// Docks and desktop windows (e.g. panels, wallpaper roots) are listed in `_NET_CLIENT_LIST` by
+/// some WMs but aren't windows a user would ever want to focus.
+fn is_dock_or_desktop(conn: &xcb::Connection, window: xcb::Window, window_type_atoms: &[xcb::Atom]) -> bool {
+    let reply = xcb::get_property(
+        conn,
+        false,
+        window,
+        window_type_atoms[0],
+        xcb::ATOM_ATOM,
+        0,
+        32,
+    )
+    .get_reply();
+
+    match reply {
+        Ok(reply) => reply
+            .value::<xcb::Atom>()
+            .iter()
+            .any(|window_type| window_type_atoms[1..].contains(window_type)),
+        Err(_) => false,
+    }
+}
+
+/// Query the root window directly over XCB, so this backend works under any `_NET`-compliant
+/// window manager (bspwm, openbox, awesome, ...) rather than being tied to one WM's IPC protocol.
+pub fn get_windows() -> Vec<DesktopWindow> {
+    let (conn, screen_num) = xcb::Connection::connect(None).unwrap();
+    let setup = conn.get_setup();
+    let screen = setup.roots().nth(screen_num as usize).unwrap();
+
+    let net_client_list = atom(&conn, "_NET_CLIENT_LIST");
+    let net_wm_name = atom(&conn, "_NET_WM_NAME");
+    let utf8_string = atom(&conn, "UTF8_STRING");
+    let window_type_atoms = [
+        atom(&conn, "_NET_WM_WINDOW_TYPE"),
+        atom(&conn, "_NET_WM_WINDOW_TYPE_DOCK"),
+        atom(&conn, "_NET_WM_WINDOW_TYPE_DESKTOP"),
+    ];
+
+    let client_list = xcb::get_property(
+        &conn,
+        false,
+        screen.root(),
+        net_client_list,
+        xcb::ATOM_WINDOW,
+        0,
+        1024,
+    )
+    .get_reply()
+    .expect("Couldn't read _NET_CLIENT_LIST; is the window manager EWMH-compliant?");
+
+    let mut desktop_windows = Vec::new();
+    for &window in client_list.value::<xcb::Window>() {
+        if is_dock_or_desktop(&conn, window, &window_type_atoms) {
+            continue;
+        }
+
+        // The window might have disappeared since _NET_CLIENT_LIST was read; skip it rather than
+        // failing the whole run.
+        let geometry = match xcb::get_geometry(&conn, window).get_reply() {
+            Ok(geometry) => geometry,
+            Err(_) => continue,
+        };
+        // src_x/src_y is a point in `window`'s own coordinate space, not its position relative to
+        // its parent -- (0, 0) asks "where is this window's origin in root space". Passing
+        // geometry.x()/y() here would double-count the offset for root-parented windows and add
+        // a decorating WM's frame offset on top of an already-wrong base for reparented ones.
+        let translated = match xcb::translate_coordinates(&conn, window, screen.root(), 0, 0)
+            .get_reply()
+        {
+            Ok(translated) => translated,
+            Err(_) => continue,
+        };
+
+        desktop_windows.push(DesktopWindow {
+            id: i64::from(window),
+            title: window_title(&conn, window, net_wm_name, utf8_string),
+            pos: (i32::from(translated.dst_x()), i32::from(translated.dst_y())),
+            size: (i32::from(geometry.width()), i32::from(geometry.height())),
+        });
+    }
+
+    desktop_windows
+}
+
+pub fn focus_window(window: &DesktopWindow) {
+    let (conn, screen_num) = xcb::Connection::connect(None).unwrap();
+    let setup = conn.get_setup();
+    let screen = setup.roots().nth(screen_num as usize).unwrap();
+
+    let net_active_window = atom(&conn, "_NET_ACTIVE_WINDOW");
+    let window_id = window.id as xcb::Window;
+
+    let data = xcb::ClientMessageData::from_data32([1, xcb::CURRENT_TIME, 0, 0, 0]);
+    let event = xcb::ClientMessageEvent::new(32, window_id, net_active_window, data);
+    xcb::send_event(
+        &conn,
+        false,
+        screen.root(),
+        xcb::EVENT_MASK_SUBSTRUCTURE_NOTIFY | xcb::EVENT_MASK_SUBSTRUCTURE_REDIRECT,
+        &event,
+    );
+    conn.flush();
+}